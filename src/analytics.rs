@@ -0,0 +1,159 @@
+use crate::battery::{fit_wear_trend, BatteryStatus};
+use crate::db::{Database, Reading};
+use rusqlite::Result;
+
+/// Long-term wear summary for one battery, derived from its full reading
+/// history rather than the single live sample `BatteryInfo::calc_health` uses.
+pub struct HealthReport {
+    /// Charge cycles used to fit the degradation trend.
+    pub samples: usize,
+    /// Most recent cycle's peak charge energy as a percentage of design
+    /// capacity.
+    pub wear_percent: Option<f32>,
+    /// Wh of full-charge capacity lost per month, from a least-squares fit
+    /// of peak charge energy against time.
+    pub degradation_per_month: Option<f32>,
+    /// Typical time to run the pack from full to empty, in seconds, from
+    /// the most recent peak energy and the average discharge power.
+    pub est_full_runtime_secs: Option<u32>,
+}
+
+const WEAR_THRESHOLD_FRACTION: f32 = 0.8;
+
+#[derive(PartialEq, Clone, Copy)]
+enum Phase {
+    Charge,
+    Discharge,
+}
+
+/// `Full` counts as part of a charge phase; `Unknown`/`NotCharging` are
+/// ambiguous and left out of the state machine entirely.
+fn phase_of(status: &BatteryStatus) -> Option<Phase> {
+    match status {
+        BatteryStatus::Charging | BatteryStatus::Full => Some(Phase::Charge),
+        BatteryStatus::Discharging => Some(Phase::Discharge),
+        BatteryStatus::Unknown | BatteryStatus::NotCharging => None,
+    }
+}
+
+/// Walks `readings` (oldest first), carrying the last definite charge
+/// phase forward across gaps. Returns the peak `energy_now` reached in
+/// each completed charge phase, alongside every `power_now` seen while
+/// discharging.
+fn segment_cycles(readings: &[Reading]) -> (Vec<(i64, f32)>, Vec<f32>) {
+    let mut peaks = Vec::new();
+    let mut discharge_power = Vec::new();
+
+    let mut phase = None;
+    let mut peak: Option<(i64, f32)> = None;
+
+    for r in readings {
+        let Some(this_phase) = phase_of(&r.status) else {
+            continue;
+        };
+
+        if this_phase == Phase::Charge {
+            if let Some(energy) = r.energy_now {
+                if peak.map_or(true, |(_, pe)| energy > pe) {
+                    peak = Some((r.timestamp, energy));
+                }
+            }
+        } else if phase == Some(Phase::Charge) {
+            if let Some(p) = peak.take() {
+                peaks.push(p);
+            }
+        }
+
+        if this_phase == Phase::Discharge {
+            if let Some(power) = r.power_now {
+                discharge_power.push(power);
+            }
+        }
+
+        phase = Some(this_phase);
+    }
+
+    (peaks, discharge_power)
+}
+
+/// Drops values more than 3 median-absolute-deviations from the median.
+/// A no-op below 4 samples, where that spread estimate isn't meaningful.
+fn reject_outliers(values: &[f32]) -> Vec<f32> {
+    if values.len() < 4 {
+        return values.to_vec();
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    let mut deviations: Vec<f32> = sorted.iter().map(|v| (v - median).abs()).collect();
+    deviations.sort_by(|a, b| a.total_cmp(b));
+    let mad = deviations[deviations.len() / 2];
+
+    if mad < f32::EPSILON {
+        return values.to_vec();
+    }
+
+    values
+        .iter()
+        .copied()
+        .filter(|v| (v - median).abs() <= 3.0 * mad)
+        .collect()
+}
+
+fn reject_peak_outliers(peaks: Vec<(i64, f32)>) -> Vec<(i64, f32)> {
+    let energies: Vec<f32> = peaks.iter().map(|&(_, e)| e).collect();
+    let kept = reject_outliers(&energies);
+    peaks
+        .into_iter()
+        .filter(|&(_, e)| kept.contains(&e))
+        .collect()
+}
+
+/// Derives `HealthReport` for `battery` from its stored history.
+/// `design_capacity` comes from the live sysfs reading, same as
+/// `battery::fit_wear_trend`'s caller already provides.
+pub fn health_report(
+    db: &Database,
+    battery: &str,
+    design_capacity: f32,
+) -> Result<Option<HealthReport>> {
+    let readings = db.recent_readings(battery, i64::MIN)?;
+    if readings.is_empty() {
+        return Ok(None);
+    }
+
+    let (peaks, discharge_power) = segment_cycles(&readings);
+    let peaks = reject_peak_outliers(peaks);
+    if peaks.is_empty() {
+        return Ok(None);
+    }
+
+    let discharge_power = reject_outliers(&discharge_power);
+
+    let wear_percent = peaks.last().map(|&(_, energy)| energy / design_capacity * 100.0);
+
+    let degradation_per_month = fit_wear_trend(&peaks, design_capacity, WEAR_THRESHOLD_FRACTION)
+        .map(|trend| trend.wear_per_month);
+
+    let avg_discharge_power = if discharge_power.is_empty() {
+        None
+    } else {
+        Some(discharge_power.iter().sum::<f32>() / discharge_power.len() as f32)
+    };
+
+    let est_full_runtime_secs = match (peaks.last(), avg_discharge_power) {
+        (Some(&(_, peak_energy)), Some(power)) if power > 0.0 => {
+            Some((peak_energy / power * 3600.0) as u32)
+        }
+        _ => None,
+    };
+
+    Ok(Some(HealthReport {
+        samples: peaks.len(),
+        wear_percent,
+        degradation_per_month,
+        est_full_runtime_secs,
+    }))
+}
@@ -1,8 +1,11 @@
 use core::fmt;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs, io};
 
+use crate::db::Database;
+
 const MICRO: f32 = 1e-6;
 const PICO: f32 = 1e-12;
 
@@ -30,6 +33,20 @@ impl fmt::Display for BatteryStatus {
     }
 }
 
+impl BatteryStatus {
+    /// Stable numeric code for machine-readable output (e.g. `--json`),
+    /// independent of the `Display` string.
+    pub fn code(&self) -> u8 {
+        match self {
+            BatteryStatus::Unknown => 0,
+            BatteryStatus::Charging => 1,
+            BatteryStatus::Discharging => 2,
+            BatteryStatus::Full => 3,
+            BatteryStatus::NotCharging => 4,
+        }
+    }
+}
+
 impl std::str::FromStr for BatteryStatus {
     type Err = ();
 
@@ -54,6 +71,8 @@ pub struct BatteryInfo {
     pub energy_full: Option<f32>,
     pub energy_full_design: Option<f32>,
     pub technology: Option<String>,
+    pub charge_start_threshold: Option<u32>,
+    pub charge_end_threshold: Option<u32>,
 }
 
 impl BatteryInfo {
@@ -82,6 +101,203 @@ impl BatteryInfo {
         let minutes = hours.fract() * 60.0;
         Some((hours as u32, minutes as u32))
     }
+
+    /// Like `calc_remaining_time`, but divides by a discharge/charge rate
+    /// smoothed over the last few minutes of stored readings instead of the
+    /// single, often-jittery `power_now` sample. Falls back to the
+    /// instantaneous calculation when there isn't enough history yet.
+    pub fn calc_smoothed_remaining_time(&self, db: &Database) -> Option<(u32, u32)> {
+        const WINDOW_SECS: i64 = 10 * 60;
+        const DECAY: f32 = 0.7;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        let readings = db.recent_readings(&self.name, now - WINDOW_SECS).ok()?;
+
+        let mut rates = Vec::new();
+        for pair in readings.windows(2) {
+            let (prev, cur) = (&pair[0], &pair[1]);
+            let dt = (cur.timestamp - prev.timestamp) as f32;
+            if dt <= 0.0 {
+                continue;
+            }
+            let (Some(prev_energy), Some(cur_energy)) = (prev.energy_now, cur.energy_now) else {
+                continue;
+            };
+
+            // Only keep pairs whose energy moved the direction `self.status`
+            // implies; a charge/discharge transition inside the window
+            // would otherwise fold a rate from the wrong direction in.
+            let delta = prev_energy - cur_energy;
+            let consistent = match self.status {
+                BatteryStatus::Charging => delta < 0.0,
+                BatteryStatus::Discharging => delta > 0.0,
+                _ => false,
+            };
+            if !consistent {
+                continue;
+            }
+
+            let rate = delta.abs() / dt * 3600.0;
+            if rate > 0.0 {
+                rates.push(rate);
+            }
+        }
+
+        if rates.len() < 2 {
+            return self.calc_remaining_time();
+        }
+
+        // Exponentially weighted moving average, most recent sample weighted highest.
+        let mut weight = 1.0;
+        let mut weighted_sum = 0.0;
+        let mut weight_total = 0.0;
+        for rate in rates.iter().rev() {
+            weighted_sum += rate * weight;
+            weight_total += weight;
+            weight *= DECAY;
+        }
+        let smoothed_rate = weighted_sum / weight_total;
+        if smoothed_rate <= 0.0 {
+            return self.calc_remaining_time();
+        }
+
+        let energy_now = self.energy_now?;
+        let energy = if self.status == BatteryStatus::Charging {
+            self.energy_full? - energy_now
+        } else {
+            energy_now
+        };
+
+        let hours = energy / smoothed_rate;
+        let minutes = hours.fract() * 60.0;
+        Some((hours as u32, minutes as u32))
+    }
+}
+
+/// Folds several `BatteryInfo`s (e.g. a ThinkPad's two internal packs) into
+/// one synthetic pack so callers can report a single combined figure.
+pub fn aggregate_batteries(infos: &[BatteryInfo]) -> Option<BatteryInfo> {
+    if infos.is_empty() {
+        return None;
+    }
+
+    let energy_now = sum_options(infos.iter().map(|i| i.energy_now));
+    let energy_full = sum_options(infos.iter().map(|i| i.energy_full));
+    let energy_full_design = sum_options(infos.iter().map(|i| i.energy_full_design));
+    let power_now = sum_options(infos.iter().map(|i| i.power_now));
+
+    let capacity = match (energy_now, energy_full) {
+        (Some(now), Some(full)) if full > 0.0 => Some((100.0 * now / full).round() as u32),
+        _ => {
+            let caps: Vec<u32> = infos.iter().filter_map(|i| i.capacity).collect();
+            if caps.is_empty() {
+                None
+            } else {
+                Some((caps.iter().sum::<u32>() as f32 / caps.len() as f32).round() as u32)
+            }
+        }
+    };
+
+    let status = if infos.iter().any(|i| i.status == BatteryStatus::Charging) {
+        BatteryStatus::Charging
+    } else if infos.iter().any(|i| i.status == BatteryStatus::Discharging) {
+        BatteryStatus::Discharging
+    } else if infos.iter().any(|i| i.status == BatteryStatus::Full) {
+        BatteryStatus::Full
+    } else {
+        BatteryStatus::Unknown
+    };
+
+    let cycle_count = infos.iter().filter_map(|i| i.cycle_count).max();
+
+    Some(BatteryInfo {
+        name: "Combined".to_string(),
+        status,
+        capacity,
+        cycle_count,
+        power_now,
+        energy_now,
+        energy_full,
+        energy_full_design,
+        technology: None,
+        charge_start_threshold: None,
+        charge_end_threshold: None,
+    })
+}
+
+fn sum_options(values: impl Iterator<Item = Option<f32>>) -> Option<f32> {
+    let mut total = 0.0;
+    let mut any = false;
+    for v in values {
+        if let Some(v) = v {
+            total += v;
+            any = true;
+        }
+    }
+    any.then_some(total)
+}
+
+/// Linear fit of a pack's peak energy-when-full against time, giving a wear
+/// rate and a projected date when capacity drops below some threshold of
+/// its design capacity.
+pub struct WearTrend {
+    /// Wh lost per 30-day month (positive means the pack is fading).
+    pub wear_per_month: f32,
+    /// Unix timestamp at which the fit crosses the threshold, if the pack
+    /// is trending downward.
+    pub projected_eol: Option<i64>,
+}
+
+const SECS_PER_MONTH: f64 = 30.0 * 86400.0;
+const SECS_PER_DAY: i64 = 86400;
+
+/// Fits `samples` (`(timestamp, energy_full_proxy)`, oldest first) with a
+/// least-squares line and reports the monthly wear rate plus the time at
+/// which the fit crosses `threshold` of `design_capacity`. Returns `None`
+/// without at least 5 distinct days of samples or a degenerate (near-zero
+/// time spread) fit.
+pub fn fit_wear_trend(
+    samples: &[(i64, f32)],
+    design_capacity: f32,
+    threshold_fraction: f32,
+) -> Option<WearTrend> {
+    let distinct_days: std::collections::HashSet<i64> =
+        samples.iter().map(|(t, _)| t / SECS_PER_DAY).collect();
+    if distinct_days.len() < 5 {
+        return None;
+    }
+
+    let n = samples.len() as f64;
+    let (sum_t, sum_y, sum_tt, sum_ty) = samples.iter().fold(
+        (0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64),
+        |(st, sy, stt, sty), &(t, y)| {
+            let t = t as f64;
+            let y = y as f64;
+            (st + t, sy + y, stt + t * t, sty + t * y)
+        },
+    );
+
+    let denom = n * sum_tt - sum_t * sum_t;
+    if denom.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let slope = (n * sum_ty - sum_t * sum_y) / denom;
+    let intercept = (sum_y - slope * sum_t) / n;
+
+    let wear_per_month = (-slope * SECS_PER_MONTH) as f32;
+
+    let threshold = design_capacity as f64 * threshold_fraction as f64;
+    let projected_eol = (slope < 0.0).then(|| ((threshold - intercept) / slope) as i64);
+
+    Some(WearTrend {
+        wear_per_month,
+        projected_eol,
+    })
 }
 
 fn read_sysfs(path: impl AsRef<Path>) -> io::Result<String> {
@@ -172,6 +388,14 @@ pub fn get_battery_info(path: &Path) -> BatteryInfo {
 
     let technology: Option<String> = read_sysfs(path.join("technology")).ok();
 
+    let charge_start_threshold: Option<u32> =
+        read_sysfs(path.join("charge_control_start_threshold"))
+            .ok()
+            .and_then(|s| s.parse().ok());
+    let charge_end_threshold: Option<u32> = read_sysfs(path.join("charge_control_end_threshold"))
+        .ok()
+        .and_then(|s| s.parse().ok());
+
     BatteryInfo {
         name,
         status,
@@ -182,5 +406,165 @@ pub fn get_battery_info(path: &Path) -> BatteryInfo {
         energy_full,
         energy_full_design,
         technology,
+        charge_start_threshold,
+        charge_end_threshold,
+    }
+}
+
+/// Writes `charge_control_start_threshold`/`charge_control_end_threshold`
+/// under a battery's sysfs directory. Either bound may be omitted to leave
+/// it unchanged. These files are root-owned on every driver that exposes
+/// them, so a permission error is reported with a clear, actionable message
+/// rather than the raw `io::Error`.
+pub fn set_charge_thresholds(
+    path: &Path,
+    start: Option<u32>,
+    end: Option<u32>,
+) -> io::Result<()> {
+    let write_threshold = |file: &str, value: u32| -> io::Result<()> {
+        fs::write(path.join(file), value.to_string()).map_err(|e| {
+            if e.kind() == io::ErrorKind::PermissionDenied {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("writing {} requires root (try running with sudo)", file),
+                )
+            } else {
+                e
+            }
+        })
+    };
+
+    if let Some(start) = start {
+        write_threshold("charge_control_start_threshold", start)?;
+    }
+    if let Some(end) = end {
+        write_threshold("charge_control_end_threshold", end)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bi(status: BatteryStatus, energy_now: Option<f32>, energy_full: Option<f32>) -> BatteryInfo {
+        BatteryInfo {
+            name: "BAT0".to_string(),
+            status,
+            capacity: None,
+            cycle_count: None,
+            power_now: None,
+            energy_now,
+            energy_full,
+            energy_full_design: None,
+            technology: None,
+            charge_start_threshold: None,
+            charge_end_threshold: None,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_batteries_empty_returns_none() {
+        assert!(aggregate_batteries(&[]).is_none());
+    }
+
+    #[test]
+    fn test_aggregate_batteries_sums_energy_and_prefers_active_status() {
+        let a = bi(BatteryStatus::Discharging, Some(20.0), Some(40.0));
+        let b = bi(BatteryStatus::Charging, Some(10.0), Some(40.0));
+
+        let combined = aggregate_batteries(&[a, b]).unwrap();
+
+        assert_eq!(combined.name, "Combined");
+        assert_eq!(combined.energy_now, Some(30.0));
+        assert_eq!(combined.energy_full, Some(80.0));
+        assert_eq!(combined.capacity, Some(38));
+        assert_eq!(combined.status, BatteryStatus::Charging);
+    }
+
+    fn now_secs() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
+    #[test]
+    fn test_calc_smoothed_remaining_time_falls_back_below_two_rates() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        let now = now_secs();
+        db.insert_reading("BAT0", now, "Discharging", Some(50), Some(10.0), Some(20.0))
+            .unwrap();
+
+        let info = BatteryInfo {
+            name: "BAT0".to_string(),
+            status: BatteryStatus::Discharging,
+            capacity: Some(50),
+            cycle_count: None,
+            power_now: Some(10.0),
+            energy_now: Some(20.0),
+            energy_full: Some(40.0),
+            energy_full_design: None,
+            technology: None,
+            charge_start_threshold: None,
+            charge_end_threshold: None,
+        };
+
+        // Only one stored reading means zero rate pairs, so this should fall
+        // back to the instantaneous calculation rather than panicking or
+        // dividing by an empty average.
+        assert_eq!(info.calc_smoothed_remaining_time(&db), info.calc_remaining_time());
+    }
+
+    #[test]
+    fn test_calc_smoothed_remaining_time_ignores_direction_inconsistent_pairs() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        let now = now_secs();
+        // Readings drawn as if charging (energy climbing), but the battery is
+        // reported as discharging below: every pair is direction-inconsistent
+        // and should be filtered out, forcing a fallback.
+        for (offset, energy) in [(-180, 10.0), (-120, 15.0), (-60, 20.0), (0, 25.0)] {
+            db.insert_reading("BAT0", now + offset, "Charging", Some(50), Some(10.0), Some(energy))
+                .unwrap();
+        }
+
+        let info = BatteryInfo {
+            name: "BAT0".to_string(),
+            status: BatteryStatus::Discharging,
+            capacity: Some(50),
+            cycle_count: None,
+            power_now: Some(10.0),
+            energy_now: Some(25.0),
+            energy_full: Some(40.0),
+            energy_full_design: None,
+            technology: None,
+            charge_start_threshold: None,
+            charge_end_threshold: None,
+        };
+
+        assert_eq!(info.calc_smoothed_remaining_time(&db), info.calc_remaining_time());
+    }
+
+    #[test]
+    fn test_fit_wear_trend_needs_five_distinct_days() {
+        let samples: Vec<(i64, f32)> = (0..4)
+            .map(|day| (day * SECS_PER_DAY, 40.0 - day as f32))
+            .collect();
+
+        assert!(fit_wear_trend(&samples, 40.0, 0.8).is_none());
+    }
+
+    #[test]
+    fn test_fit_wear_trend_reports_monthly_wear_rate() {
+        let samples: Vec<(i64, f32)> = (0..6)
+            .map(|day| (day * SECS_PER_DAY, 40.0 - day as f32))
+            .collect();
+
+        let trend = fit_wear_trend(&samples, 40.0, 0.8).unwrap();
+
+        assert!(trend.wear_per_month > 0.0, "capacity is fading over time");
+        assert!(trend.projected_eol.is_some());
     }
 }
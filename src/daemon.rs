@@ -1,11 +1,21 @@
-use crate::battery::{find_batteries, get_battery_info};
+use crate::battery::{find_batteries, get_battery_info, BatteryInfo, BatteryStatus};
 use crate::db::Database;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Minimum interval between rows for a battery whose status/capacity/power
+/// haven't moved, so the table still reflects "juice was running" even
+/// through long idle stretches.
+const HEARTBEAT_SECS: i64 = 15 * 60;
+
+/// Default `power_now` change (in watts) that counts as a real move rather
+/// than sensor jitter.
+const DEFAULT_POWER_EPSILON: f32 = 1.0;
+
 fn unix_timestamp() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -13,6 +23,38 @@ fn unix_timestamp() -> i64 {
         .as_secs() as i64
 }
 
+struct LastSnapshot {
+    status: BatteryStatus,
+    capacity: Option<u32>,
+    power_now: Option<f32>,
+    timestamp: i64,
+}
+
+fn should_record(
+    last: Option<&LastSnapshot>,
+    info: &BatteryInfo,
+    timestamp: i64,
+    power_epsilon: f32,
+) -> bool {
+    let Some(last) = last else {
+        return true;
+    };
+
+    if last.status != info.status || last.capacity != info.capacity {
+        return true;
+    }
+
+    match (last.power_now, info.power_now) {
+        (Some(last_power), Some(power)) if (last_power - power).abs() > power_epsilon => {
+            return true
+        }
+        (Some(_), None) | (None, Some(_)) => return true,
+        _ => {}
+    }
+
+    timestamp - last.timestamp >= HEARTBEAT_SECS
+}
+
 pub fn run(db_path: PathBuf, interval_secs: u64) -> Result<(), Box<dyn std::error::Error>> {
     let battery_paths = find_batteries();
     if battery_paths.is_empty() {
@@ -31,19 +73,35 @@ pub fn run(db_path: PathBuf, interval_secs: u64) -> Result<(), Box<dyn std::erro
 
     println!("Daemon started (interval: {}s)", interval_secs);
 
+    let mut last_snapshots: HashMap<String, LastSnapshot> = HashMap::new();
+
     while running.load(Ordering::SeqCst) {
         let timestamp = unix_timestamp();
 
         for path in &battery_paths {
             let info = get_battery_info(path);
-            db.insert_reading(
-                &info.name,
-                timestamp,
-                &info.status.to_string(),
-                info.capacity,
-                info.power_now,
-                info.energy_now,
-            )?;
+            let last = last_snapshots.get(&info.name);
+
+            if should_record(last, &info, timestamp, DEFAULT_POWER_EPSILON) {
+                db.insert_reading(
+                    &info.name,
+                    timestamp,
+                    &info.status.to_string(),
+                    info.capacity,
+                    info.power_now,
+                    info.energy_now,
+                )?;
+
+                last_snapshots.insert(
+                    info.name.clone(),
+                    LastSnapshot {
+                        status: info.status,
+                        capacity: info.capacity,
+                        power_now: info.power_now,
+                        timestamp,
+                    },
+                );
+            }
         }
 
         thread::sleep(Duration::from_secs(interval_secs));
@@ -52,3 +110,131 @@ pub fn run(db_path: PathBuf, interval_secs: u64) -> Result<(), Box<dyn std::erro
     println!("Shutting down juice daemon...");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(status: BatteryStatus, capacity: Option<u32>, power_now: Option<f32>) -> BatteryInfo {
+        BatteryInfo {
+            name: "BAT0".to_string(),
+            status,
+            capacity,
+            cycle_count: None,
+            power_now,
+            energy_now: None,
+            energy_full: None,
+            energy_full_design: None,
+            technology: None,
+            charge_start_threshold: None,
+            charge_end_threshold: None,
+        }
+    }
+
+    #[test]
+    fn test_should_record_first_reading_always_records() {
+        let new = info(BatteryStatus::Discharging, Some(80), Some(10.0));
+        assert!(should_record(None, &new, 1000, DEFAULT_POWER_EPSILON));
+    }
+
+    #[test]
+    fn test_should_record_power_move_within_epsilon_is_not_recorded() {
+        let last = LastSnapshot {
+            status: BatteryStatus::Discharging,
+            capacity: Some(80),
+            power_now: Some(10.0),
+            timestamp: 1000,
+        };
+        let new = info(BatteryStatus::Discharging, Some(80), Some(10.5));
+
+        assert!(!should_record(Some(&last), &new, 1001, DEFAULT_POWER_EPSILON));
+    }
+
+    #[test]
+    fn test_should_record_power_move_past_epsilon_is_recorded() {
+        let last = LastSnapshot {
+            status: BatteryStatus::Discharging,
+            capacity: Some(80),
+            power_now: Some(10.0),
+            timestamp: 1000,
+        };
+        let new = info(BatteryStatus::Discharging, Some(80), Some(11.5));
+
+        assert!(should_record(Some(&last), &new, 1001, DEFAULT_POWER_EPSILON));
+    }
+
+    #[test]
+    fn test_should_record_heartbeat_fires_at_exactly_the_interval() {
+        let last = LastSnapshot {
+            status: BatteryStatus::Discharging,
+            capacity: Some(80),
+            power_now: Some(10.0),
+            timestamp: 1000,
+        };
+        let unchanged = info(BatteryStatus::Discharging, Some(80), Some(10.0));
+
+        assert!(!should_record(
+            Some(&last),
+            &unchanged,
+            1000 + HEARTBEAT_SECS - 1,
+            DEFAULT_POWER_EPSILON
+        ));
+        assert!(should_record(
+            Some(&last),
+            &unchanged,
+            1000 + HEARTBEAT_SECS,
+            DEFAULT_POWER_EPSILON
+        ));
+    }
+}
+
+/// Polls batteries at `interval_secs` and prints a line every time a
+/// battery's status or capacity changes, giving a tail-style live view
+/// without touching the database.
+pub fn watch(interval_secs: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let battery_paths = find_batteries();
+    if battery_paths.is_empty() {
+        return Err("No battery found".into());
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let mut last_snapshots: HashMap<String, (BatteryStatus, Option<u32>)> = HashMap::new();
+
+    while running.load(Ordering::SeqCst) {
+        for path in &battery_paths {
+            let info = get_battery_info(path);
+            let previous = last_snapshots.get(&info.name);
+            let status_changed = previous.is_some_and(|(status, _)| *status != info.status);
+            let capacity_changed = previous.is_some_and(|(_, capacity)| *capacity != info.capacity);
+
+            if previous.is_none() || status_changed || capacity_changed {
+                let time = crate::format_timestamp(unix_timestamp());
+                let capacity_str = info
+                    .capacity
+                    .map(|c| format!("{}%", c))
+                    .unwrap_or_else(|| "--%".to_string());
+
+                if status_changed {
+                    let prev_status = &previous.unwrap().0;
+                    println!(
+                        "{} {} {} {} → {}",
+                        time, info.name, capacity_str, prev_status, info.status
+                    );
+                } else {
+                    println!("{} {} {} {}", time, info.name, capacity_str, info.status);
+                }
+
+                last_snapshots.insert(info.name.clone(), (info.status.clone(), info.capacity));
+            }
+        }
+
+        thread::sleep(Duration::from_secs(interval_secs));
+    }
+
+    Ok(())
+}
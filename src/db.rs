@@ -1,16 +1,147 @@
 use directories::ProjectDirs;
 use rusqlite::{Connection, Result};
+use sha2::{Digest, Sha256};
+use std::error::Error;
 use std::path::PathBuf;
 
 use crate::battery::BatteryStatus;
 
 pub struct Reading {
+    pub id: i64,
     pub battery: String,
     pub timestamp: i64,
     pub status: BatteryStatus,
     pub capacity: Option<u32>,
     pub power_now: Option<f32>,
     pub energy_now: Option<f32>,
+    pub content_hash: String,
+    pub synced: bool,
+}
+
+/// Stable content hash for a reading, used to dedup rows merged in from a
+/// sync peer regardless of clock skew or which machine inserted them
+/// first. Two readings with identical fields hash identically, by design.
+fn content_hash(
+    battery: &str,
+    timestamp: i64,
+    status: &str,
+    capacity: Option<u32>,
+    power_now: Option<f32>,
+    energy_now: Option<f32>,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(battery.as_bytes());
+    hasher.update(timestamp.to_le_bytes());
+    hasher.update(status.as_bytes());
+    hasher.update(capacity.unwrap_or(u32::MAX).to_le_bytes());
+    hasher.update(power_now.unwrap_or(f32::NAN).to_le_bytes());
+    hasher.update(energy_now.unwrap_or(f32::NAN).to_le_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Ordered, embedded schema migrations. Each entry is one SQL batch; its
+/// position (1-indexed) is the schema version it brings the database to.
+/// Entries 1 and 2 are idempotent (`IF NOT EXISTS`); entry 3's
+/// `ALTER TABLE ... ADD COLUMN` is not, and fails if run a second time, so
+/// it relies on `migrate` skipping versions `<= user_version`. Append here
+/// to evolve the schema — never edit or remove an already-shipped entry.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE IF NOT EXISTS readings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp INTEGER NOT NULL,
+        battery TEXT NOT NULL,
+        status TEXT,
+        capacity INTEGER,
+        power_now REAL,
+        energy_now REAL
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_readings_timestamp
+        ON readings(timestamp);
+    CREATE INDEX IF NOT EXISTS idx_readings_battery_time
+        ON readings(battery, timestamp);
+    ",
+    // v2: rollup tables for `compact()`, avoiding unbounded raw-row growth.
+    "
+    CREATE TABLE IF NOT EXISTS readings_hourly (
+        battery TEXT NOT NULL,
+        bucket_start INTEGER NOT NULL,
+        capacity_avg REAL,
+        capacity_min INTEGER,
+        capacity_max INTEGER,
+        power_now_avg REAL,
+        power_now_min REAL,
+        power_now_max REAL,
+        energy_now_avg REAL,
+        energy_now_min REAL,
+        energy_now_max REAL,
+        sample_count INTEGER NOT NULL,
+        PRIMARY KEY (battery, bucket_start)
+    );
+
+    CREATE TABLE IF NOT EXISTS readings_daily (
+        battery TEXT NOT NULL,
+        bucket_start INTEGER NOT NULL,
+        capacity_avg REAL,
+        capacity_min INTEGER,
+        capacity_max INTEGER,
+        power_now_avg REAL,
+        power_now_min REAL,
+        power_now_max REAL,
+        energy_now_avg REAL,
+        energy_now_min REAL,
+        energy_now_max REAL,
+        sample_count INTEGER NOT NULL,
+        PRIMARY KEY (battery, bucket_start)
+    );
+    ",
+    // v3: sync support — a stable content hash for cross-machine dedup,
+    // and a synced flag so a sync client knows what it hasn't pushed yet.
+    "
+    ALTER TABLE readings ADD COLUMN content_hash TEXT;
+    ALTER TABLE readings ADD COLUMN synced INTEGER NOT NULL DEFAULT 0;
+
+    -- Rows inserted before this migration have no real content hash; give
+    -- them a unique placeholder so dedup and the index still work, rather
+    -- than leaving content_hash NULL.
+    UPDATE readings SET content_hash = 'legacy-' || id WHERE content_hash IS NULL;
+
+    CREATE INDEX IF NOT EXISTS idx_readings_content_hash
+        ON readings(content_hash);
+    ",
+];
+
+/// Bucket width, in seconds, of each rollup table, finest first.
+const HOURLY_BUCKET_SECS: i64 = 3600;
+const DAILY_BUCKET_SECS: i64 = 86400;
+
+/// Above this span, `get_readings_rollup(Auto)` prefers the daily table so
+/// a multi-month query doesn't have to scan an hourly row per hour.
+const AUTO_DAILY_THRESHOLD_SECS: i64 = 14 * DAILY_BUCKET_SECS;
+
+/// One bucket's aggregated readings from a rollup table.
+pub struct RollupReading {
+    pub battery: String,
+    pub bucket_start: i64,
+    pub capacity_avg: Option<f32>,
+    pub capacity_min: Option<u32>,
+    pub capacity_max: Option<u32>,
+    pub power_now_avg: Option<f32>,
+    pub power_now_min: Option<f32>,
+    pub power_now_max: Option<f32>,
+    pub energy_now_avg: Option<f32>,
+    pub energy_now_min: Option<f32>,
+    pub energy_now_max: Option<f32>,
+    pub sample_count: i64,
+}
+
+/// Which rollup granularity to read with `get_readings_rollup`.
+pub enum RollupResolution {
+    /// Pick hourly or daily automatically based on the requested span.
+    Auto,
+    Hourly,
+    Daily,
 }
 
 pub struct Database {
@@ -18,31 +149,56 @@ pub struct Database {
 }
 
 impl Database {
-    pub fn open(path: &PathBuf) -> Result<Self> {
+    pub fn open(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
         let conn = Connection::open(path)?;
-        Ok(Self { conn })
+        let db = Self { conn };
+        db.migrate()?;
+        Ok(db)
     }
 
-    pub fn init_scheme(&self) -> Result<()> {
-        self.conn.execute_batch(
-            "
-                CREATE TABLE IF NOT EXISTS readings (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    timestamp INTEGER NOT NULL,
-                    battery TEXT NOT NULL,
-                    status TEXT,
-                    capacity INTEGER,
-                    power_now REAL,
-                    energy_now REAL
-                );
-
-                CREATE INDEX IF NOT EXISTS idx_readings_timestamp
-                    ON readings(timestamp);
-                CREATE INDEX IF NOT EXISTS idx_readings_battery_time
-                    ON readings(battery, timestamp);
-            ",
-        )?;
-        Ok(())
+    /// Brings the database up to the latest embedded schema version,
+    /// tracked via SQLite's `PRAGMA user_version`. Each pending migration
+    /// runs in its own transaction so a failure partway through rolls back
+    /// instead of leaving the schema half-upgraded, and a migration whose
+    /// number is already `<= user_version` is never re-run. Returns the
+    /// resulting version.
+    ///
+    /// Refuses to touch a database stamped with a version newer than this
+    /// build knows about, rather than risk corrupting it.
+    pub fn migrate(&self) -> Result<u32, Box<dyn Error>> {
+        let current: u32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        let latest = MIGRATIONS.len() as u32;
+        if current > latest {
+            return Err(format!(
+                "database schema version {} is newer than this build of juice supports (max {})",
+                current, latest
+            )
+            .into());
+        }
+
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            let version = (i + 1) as u32;
+            if version <= current {
+                continue;
+            }
+
+            self.conn.execute_batch(&format!(
+                "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                migration, version
+            ))?;
+        }
+
+        Ok(latest.max(current))
+    }
+
+    /// Retained for existing call sites; schema setup now happens
+    /// automatically in `open`, so this just re-runs (idempotently) the
+    /// migration check.
+    pub fn init_scheme(&self) -> Result<(), Box<dyn Error>> {
+        self.migrate().map(|_| ())
     }
 
     pub fn insert_reading(
@@ -54,12 +210,15 @@ impl Database {
         power_now: Option<f32>,
         energy_now: Option<f32>,
     ) -> Result<()> {
+        let hash = content_hash(battery, timestamp, status, capacity, power_now, energy_now);
         self.conn.execute(
             "
             INSERT INTO readings
-            (timestamp, battery, status, capacity, power_now, energy_now)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6 )",
-            (timestamp, battery, status, capacity, power_now, energy_now),
+            (timestamp, battery, status, capacity, power_now, energy_now, content_hash)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            (
+                timestamp, battery, status, capacity, power_now, energy_now, hash,
+            ),
         )?;
         Ok(())
     }
@@ -89,13 +248,167 @@ impl Database {
             .ok()
     }
 
+    /// Returns `(timestamp, energy_now)` samples taken while the battery was
+    /// reporting `Full`, ordered oldest-first. Since the schema doesn't keep
+    /// a running `energy_full` column, a pack's peak energy each time it
+    /// tops off is the closest proxy we have for tracking capacity fade.
+    pub fn full_energy_samples(&self, battery: &str) -> Result<Vec<(i64, f32)>> {
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT timestamp, energy_now
+            FROM readings
+            WHERE battery = ?1 AND status = 'Full' AND energy_now IS NOT NULL
+            ORDER BY timestamp ASC
+            ",
+        )?;
+
+        let rows = stmt.query_map([battery], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f32>(1)?))
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Returns readings for a single battery at or after `since`, ordered
+    /// oldest-first, for callers that need a short recent window (e.g. a
+    /// smoothed remaining-time estimate) rather than the full history.
+    pub fn recent_readings(&self, battery: &str, since: i64) -> Result<Vec<Reading>> {
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT id, timestamp, battery, status, capacity, power_now, energy_now, content_hash, synced
+            FROM readings
+            WHERE battery = ?1 AND timestamp >= ?2
+            ORDER BY timestamp ASC
+            ",
+        )?;
+
+        let rows = stmt.query_map((battery, since), |row| {
+            let status_str: String = row.get(3)?;
+            Ok(Reading {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                battery: row.get(2)?,
+                status: status_str.parse().unwrap_or(BatteryStatus::Unknown),
+                capacity: row.get(4)?,
+                power_now: row.get(5)?,
+                energy_now: row.get(6)?,
+                content_hash: row.get(7)?,
+                synced: row.get::<_, i64>(8)? != 0,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Recomputes the hourly and daily rollup tables from raw `readings`
+    /// rows, then deletes the rows it rolled up. Only rows older than a
+    /// daily-bucket-aligned boundary are touched, so every bucket rolled
+    /// up is complete (no more raw rows can ever land in it) — a row is
+    /// never dropped unrolled, and a bucket is never finalized twice from
+    /// a partial set.
+    pub fn compact(&self, now: i64, retention_secs: i64) -> Result<()> {
+        let cutoff = now - retention_secs;
+        let boundary = cutoff - cutoff.rem_euclid(DAILY_BUCKET_SECS);
+
+        self.rollup_into("readings_hourly", HOURLY_BUCKET_SECS, boundary)?;
+        self.rollup_into("readings_daily", DAILY_BUCKET_SECS, boundary)?;
+
+        self.conn
+            .execute("DELETE FROM readings WHERE timestamp < ?1", [boundary])?;
+
+        Ok(())
+    }
+
+    fn rollup_into(&self, table: &str, bucket_secs: i64, boundary: i64) -> Result<()> {
+        self.conn.execute(
+            &format!(
+                "
+                INSERT INTO {table}
+                    (battery, bucket_start, capacity_avg, capacity_min, capacity_max,
+                     power_now_avg, power_now_min, power_now_max,
+                     energy_now_avg, energy_now_min, energy_now_max, sample_count)
+                SELECT battery,
+                       timestamp - (timestamp % ?1) AS bucket_start,
+                       AVG(capacity), MIN(capacity), MAX(capacity),
+                       AVG(power_now), MIN(power_now), MAX(power_now),
+                       AVG(energy_now), MIN(energy_now), MAX(energy_now),
+                       COUNT(*)
+                FROM readings
+                WHERE timestamp < ?2
+                GROUP BY battery, bucket_start
+                ON CONFLICT(battery, bucket_start) DO UPDATE SET
+                    capacity_avg = excluded.capacity_avg,
+                    capacity_min = excluded.capacity_min,
+                    capacity_max = excluded.capacity_max,
+                    power_now_avg = excluded.power_now_avg,
+                    power_now_min = excluded.power_now_min,
+                    power_now_max = excluded.power_now_max,
+                    energy_now_avg = excluded.energy_now_avg,
+                    energy_now_min = excluded.energy_now_min,
+                    energy_now_max = excluded.energy_now_max,
+                    sample_count = excluded.sample_count
+                "
+            ),
+            (bucket_secs, boundary),
+        )?;
+        Ok(())
+    }
+
+    /// Reads aggregated readings over `[from, to]` from the hourly or
+    /// daily rollup table, per `resolution`.
+    pub fn get_readings_rollup(
+        &self,
+        from: i64,
+        to: i64,
+        resolution: RollupResolution,
+    ) -> Result<Vec<RollupReading>> {
+        let table = match resolution {
+            RollupResolution::Hourly => "readings_hourly",
+            RollupResolution::Daily => "readings_daily",
+            RollupResolution::Auto if to.saturating_sub(from) > AUTO_DAILY_THRESHOLD_SECS => {
+                "readings_daily"
+            }
+            RollupResolution::Auto => "readings_hourly",
+        };
+
+        let mut stmt = self.conn.prepare(&format!(
+            "
+            SELECT battery, bucket_start, capacity_avg, capacity_min, capacity_max,
+                   power_now_avg, power_now_min, power_now_max,
+                   energy_now_avg, energy_now_min, energy_now_max, sample_count
+            FROM {table}
+            WHERE bucket_start >= ?1 AND bucket_start <= ?2
+            ORDER BY battery, bucket_start ASC
+            "
+        ))?;
+
+        let rows = stmt.query_map([from, to], |row| {
+            Ok(RollupReading {
+                battery: row.get(0)?,
+                bucket_start: row.get(1)?,
+                capacity_avg: row.get(2)?,
+                capacity_min: row.get(3)?,
+                capacity_max: row.get(4)?,
+                power_now_avg: row.get(5)?,
+                power_now_min: row.get(6)?,
+                power_now_max: row.get(7)?,
+                energy_now_avg: row.get(8)?,
+                energy_now_min: row.get(9)?,
+                energy_now_max: row.get(10)?,
+                sample_count: row.get(11)?,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+    }
+
     pub fn get_readings(&self, from: Option<i64>, to: Option<i64>) -> Result<Vec<Reading>> {
         let start = from.unwrap_or(i64::MIN);
         let end = to.unwrap_or(i64::MAX);
 
         let mut stmt = self.conn.prepare(
             "
-            SELECT timestamp, battery, status, capacity, power_now, energy_now
+            SELECT id, timestamp, battery, status, capacity, power_now, energy_now, content_hash, synced
             FROM readings
             WHERE timestamp >= ?1 AND timestamp <= ?2
             ORDER BY timestamp ASC
@@ -103,14 +416,17 @@ impl Database {
         )?;
 
         let rows = stmt.query_map([start, end], |row| {
-            let status_str: String = row.get(2)?;
+            let status_str: String = row.get(3)?;
             Ok(Reading {
-                timestamp: row.get(0)?,
-                battery: row.get(1)?,
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                battery: row.get(2)?,
                 status: status_str.parse().unwrap_or(BatteryStatus::Unknown),
-                capacity: row.get(3)?,
-                power_now: row.get(4)?,
-                energy_now: row.get(5)?,
+                capacity: row.get(4)?,
+                power_now: row.get(5)?,
+                energy_now: row.get(6)?,
+                content_hash: row.get(7)?,
+                synced: row.get::<_, i64>(8)? != 0,
             })
         })?;
 
@@ -118,6 +434,252 @@ impl Database {
 
         Ok(readings)
     }
+
+    /// Keyset-paginated read: returns up to `limit` readings ordered by
+    /// `(timestamp, id)`, plus an opaque cursor to pass as `after` for the
+    /// next page, or `None` once exhausted. Unlike `get_readings`, this
+    /// never materializes more than `limit` rows at a time, so it stays in
+    /// constant memory regardless of how much history has accumulated.
+    pub fn get_readings_page(
+        &self,
+        after: Option<(i64, i64)>,
+        limit: usize,
+    ) -> Result<(Vec<Reading>, Option<(i64, i64)>)> {
+        let (after_ts, after_id) = after.unwrap_or((i64::MIN, i64::MIN));
+
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT id, timestamp, battery, status, capacity, power_now, energy_now, content_hash, synced
+            FROM readings
+            WHERE (timestamp, id) > (?1, ?2)
+            ORDER BY timestamp ASC, id ASC
+            LIMIT ?3
+            ",
+        )?;
+
+        let rows = stmt.query_map((after_ts, after_id, limit as i64), |row| {
+            let status_str: String = row.get(3)?;
+            Ok(Reading {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                battery: row.get(2)?,
+                status: status_str.parse().unwrap_or(BatteryStatus::Unknown),
+                capacity: row.get(4)?,
+                power_now: row.get(5)?,
+                energy_now: row.get(6)?,
+                content_hash: row.get(7)?,
+                synced: row.get::<_, i64>(8)? != 0,
+            })
+        })?;
+
+        let readings = rows.collect::<Result<Vec<_>, _>>()?;
+
+        let cursor = readings.last().map(|r| (r.timestamp, r.id));
+        Ok((readings, cursor))
+    }
+
+    /// Streams readings in `[from, to]` to `f` via `query_map` without
+    /// collecting them, so export/analysis passes can run in constant
+    /// memory over arbitrarily large histories.
+    pub fn for_each_reading(
+        &self,
+        from: Option<i64>,
+        to: Option<i64>,
+        mut f: impl FnMut(Reading),
+    ) -> Result<()> {
+        let start = from.unwrap_or(i64::MIN);
+        let end = to.unwrap_or(i64::MAX);
+
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT id, timestamp, battery, status, capacity, power_now, energy_now, content_hash, synced
+            FROM readings
+            WHERE timestamp >= ?1 AND timestamp <= ?2
+            ORDER BY timestamp ASC
+            ",
+        )?;
+
+        let rows = stmt.query_map([start, end], |row| {
+            let status_str: String = row.get(3)?;
+            Ok(Reading {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                battery: row.get(2)?,
+                status: status_str.parse().unwrap_or(BatteryStatus::Unknown),
+                capacity: row.get(4)?,
+                power_now: row.get(5)?,
+                energy_now: row.get(6)?,
+                content_hash: row.get(7)?,
+                synced: row.get::<_, i64>(8)? != 0,
+            })
+        })?;
+
+        for row in rows {
+            f(row?);
+        }
+
+        Ok(())
+    }
+
+    /// Bulk-inserts previously-parsed rows inside a single transaction, so
+    /// a large import doesn't pay a fsync per row. Each row keeps its
+    /// `timestamp`, but `id` is not preserved on round-trip import — each
+    /// row gets a fresh autoincrement id, matching how `insert_reading`
+    /// already works.
+    pub fn import_readings(&self, rows: &[ImportRow]) -> Result<usize> {
+        self.conn.execute_batch("BEGIN")?;
+
+        for row in rows {
+            let status = row.status.to_string();
+            let hash = content_hash(
+                &row.battery,
+                row.timestamp,
+                &status,
+                row.capacity,
+                row.power_now,
+                row.energy_now,
+            );
+
+            let result = self.conn.execute(
+                "
+                INSERT INTO readings
+                (timestamp, battery, status, capacity, power_now, energy_now, content_hash)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                (
+                    row.timestamp,
+                    &row.battery,
+                    status,
+                    row.capacity,
+                    row.power_now,
+                    row.energy_now,
+                    hash,
+                ),
+            );
+
+            if let Err(e) = result {
+                self.conn.execute_batch("ROLLBACK")?;
+                return Err(e);
+            }
+        }
+
+        self.conn.execute_batch("COMMIT")?;
+        Ok(rows.len())
+    }
+
+    /// Readings not yet pushed to a sync peer (`synced = 0`).
+    pub fn unsynced_readings(&self) -> Result<Vec<Reading>> {
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT id, timestamp, battery, status, capacity, power_now, energy_now, content_hash, synced
+            FROM readings
+            WHERE synced = 0
+            ORDER BY timestamp ASC
+            ",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            let status_str: String = row.get(3)?;
+            Ok(Reading {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                battery: row.get(2)?,
+                status: status_str.parse().unwrap_or(BatteryStatus::Unknown),
+                capacity: row.get(4)?,
+                power_now: row.get(5)?,
+                energy_now: row.get(6)?,
+                content_hash: row.get(7)?,
+                synced: row.get::<_, i64>(8)? != 0,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Marks the given reading ids as synced, so a retried `push` won't
+    /// re-send them.
+    pub fn mark_synced(&self, ids: &[i64]) -> Result<()> {
+        // Stay well under SQLite's bound-parameter limit (as low as 999 on
+        // older builds), so a large push doesn't fail outright.
+        const CHUNK_SIZE: usize = 500;
+
+        for chunk in ids.chunks(CHUNK_SIZE) {
+            let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let sql = format!("UPDATE readings SET synced = 1 WHERE id IN ({})", placeholders);
+
+            let params = chunk
+                .iter()
+                .map(|id| id as &dyn rusqlite::ToSql)
+                .collect::<Vec<_>>();
+            self.conn.execute(&sql, params.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+    /// Merges readings pulled from a sync peer, deduping on `content_hash`
+    /// so re-running the merge never double-inserts. Returns how many rows
+    /// were newly inserted.
+    pub fn merge_remote_readings(&self, rows: &[RemoteReading]) -> Result<usize> {
+        let mut inserted = 0;
+
+        for row in rows {
+            let exists: bool = self
+                .conn
+                .query_row(
+                    "SELECT 1 FROM readings WHERE content_hash = ?1 LIMIT 1",
+                    [&row.content_hash],
+                    |_| Ok(true),
+                )
+                .unwrap_or(false);
+
+            if exists {
+                continue;
+            }
+
+            self.conn.execute(
+                "
+                INSERT INTO readings
+                (timestamp, battery, status, capacity, power_now, energy_now, content_hash, synced)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1)",
+                (
+                    row.timestamp,
+                    &row.battery,
+                    row.status.to_string(),
+                    row.capacity,
+                    row.power_now,
+                    row.energy_now,
+                    &row.content_hash,
+                ),
+            )?;
+            inserted += 1;
+        }
+
+        Ok(inserted)
+    }
+}
+
+/// A reading as carried over the wire during sync, keyed by its stable
+/// `content_hash` rather than a machine-local autoincrement id.
+pub struct RemoteReading {
+    pub timestamp: i64,
+    pub battery: String,
+    pub status: BatteryStatus,
+    pub capacity: Option<u32>,
+    pub power_now: Option<f32>,
+    pub energy_now: Option<f32>,
+    pub content_hash: String,
+}
+
+/// A row parsed from an import source, keyed to the stable column order
+/// `export_csv`/`export_json` write. The `id` and derived `datetime`
+/// columns are ignored on import.
+pub struct ImportRow {
+    pub timestamp: i64,
+    pub battery: String,
+    pub status: BatteryStatus,
+    pub capacity: Option<u32>,
+    pub power_now: Option<f32>,
+    pub energy_now: Option<f32>,
 }
 
 pub fn default_db_path() -> PathBuf {
@@ -152,4 +714,141 @@ mod tests {
 
         assert_eq!(db.count_readings().unwrap(), 1);
     }
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        let version = db.migrate().unwrap();
+        assert_eq!(version, MIGRATIONS.len() as u32);
+        assert_eq!(db.migrate().unwrap(), version);
+    }
+
+    #[test]
+    fn test_get_readings_page_covers_every_row_once() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        for i in 0..5 {
+            db.insert_reading("BAT0", 1000 + i, "Discharging", Some(80), Some(10.0), Some(40.0))
+                .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next) = db.get_readings_page(cursor, 2).unwrap();
+            if page.is_empty() {
+                break;
+            }
+            seen.extend(page.iter().map(|r| r.timestamp));
+            cursor = next;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(seen, vec![1000, 1001, 1002, 1003, 1004]);
+    }
+
+    #[test]
+    fn test_compact_rollup_is_idempotent_and_readable() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        db.insert_reading("BAT0", 0, "Discharging", Some(80), Some(10.0), Some(40.0))
+            .unwrap();
+        db.insert_reading("BAT0", 1800, "Discharging", Some(70), Some(12.0), Some(35.0))
+            .unwrap();
+
+        db.compact(100_000, 1).unwrap();
+        let first = db
+            .get_readings_rollup(0, HOURLY_BUCKET_SECS, RollupResolution::Hourly)
+            .unwrap();
+
+        db.compact(100_000, 1).unwrap();
+        let second = db
+            .get_readings_rollup(0, HOURLY_BUCKET_SECS, RollupResolution::Hourly)
+            .unwrap();
+
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].sample_count, 2);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].sample_count, 2);
+        assert_eq!(db.count_readings().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_compact_does_not_finalize_a_bucket_twice_from_a_partial_set() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        // Both readings land in the same hourly/daily bucket, but the
+        // second one isn't inserted until after an earlier compact() call
+        // whose unaligned cutoff would have fallen between them.
+        db.insert_reading("BAT0", 0, "Discharging", Some(80), Some(10.0), Some(40.0))
+            .unwrap();
+        db.compact(1_000, 0).unwrap();
+
+        db.insert_reading("BAT0", 1800, "Discharging", Some(70), Some(12.0), Some(35.0))
+            .unwrap();
+        db.compact(200_000, 0).unwrap();
+
+        let rollup = db
+            .get_readings_rollup(0, HOURLY_BUCKET_SECS, RollupResolution::Hourly)
+            .unwrap();
+
+        assert_eq!(rollup.len(), 1);
+        assert_eq!(rollup[0].sample_count, 2);
+        assert_eq!(db.count_readings().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_merge_remote_readings_dedups_by_content_hash() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        let row = RemoteReading {
+            timestamp: 1000,
+            battery: "BAT0".to_string(),
+            status: BatteryStatus::Discharging,
+            capacity: Some(80),
+            power_now: Some(10.5),
+            energy_now: Some(40.0),
+            content_hash: content_hash("BAT0", 1000, "Discharging", Some(80), Some(10.5), Some(40.0)),
+        };
+
+        let first = db.merge_remote_readings(&[row]).unwrap();
+        let row_again = RemoteReading {
+            timestamp: 1000,
+            battery: "BAT0".to_string(),
+            status: BatteryStatus::Discharging,
+            capacity: Some(80),
+            power_now: Some(10.5),
+            energy_now: Some(40.0),
+            content_hash: content_hash("BAT0", 1000, "Discharging", Some(80), Some(10.5), Some(40.0)),
+        };
+        let second = db.merge_remote_readings(&[row_again]).unwrap();
+
+        assert_eq!(first, 1);
+        assert_eq!(second, 0);
+        assert_eq!(db.count_readings().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mark_synced_chunks_past_the_placeholder_limit() {
+        let db = Database::open(&PathBuf::from(":memory:")).unwrap();
+        db.init_scheme().unwrap();
+
+        for i in 0..1200 {
+            db.insert_reading("BAT0", i, "Discharging", Some(80), Some(10.0), Some(40.0))
+                .unwrap();
+        }
+
+        let ids: Vec<i64> = db.unsynced_readings().unwrap().iter().map(|r| r.id).collect();
+        assert_eq!(ids.len(), 1200);
+
+        db.mark_synced(&ids).unwrap();
+
+        assert!(db.unsynced_readings().unwrap().is_empty());
+    }
 }
@@ -1,27 +1,248 @@
-use crate::{db::Reading, format_timestamp};
-use std::io::{self, Write};
+use crate::db::{Database, ImportRow};
+use crate::format_timestamp;
+use std::error::Error;
+use std::io::{self, BufRead, Read, Write};
 
-pub fn write_csv(mut writer: impl Write, readings: &[Reading]) -> io::Result<()> {
+/// Quotes `s` RFC 4180-style if it contains a comma or quote, so a battery
+/// name like `"My, Battery"` doesn't silently shift every column after it
+/// on import. Newlines are flattened to spaces rather than quoted, since
+/// `import_csv` reads rows one physical line at a time and an embedded
+/// newline would otherwise split a single row in two.
+fn csv_field(s: &str) -> String {
+    let s = s.replace('\n', " ");
+    if s.contains([',', '"']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+/// Streams `db`'s readings in `[from, to]` to `w` as CSV, one row at a
+/// time via `Database::for_each_reading`, so exporting years of history
+/// doesn't require holding it all in memory at once. Column order matches
+/// the schema plus the derived `datetime` column.
+pub fn export_csv(
+    db: &Database,
+    mut w: impl Write,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<(), Box<dyn Error>> {
     writeln!(
-        writer,
-        "timestamp,datetime,battery,status,capacity,power_now,energy_now"
+        w,
+        "id,timestamp,datetime,battery,status,capacity,power_now,energy_now"
     )?;
 
-    for r in readings {
-        writeln!(
-            writer,
-            "{},{},{},{},{},{},{},",
+    let mut write_err = None;
+    db.for_each_reading(from, to, |r| {
+        if write_err.is_some() {
+            return;
+        }
+        let result = writeln!(
+            w,
+            "{},{},{},{},{},{},{},{}",
+            r.id,
             r.timestamp,
             format_timestamp(r.timestamp),
-            r.battery,
+            csv_field(&r.battery),
             r.status,
             r.capacity.map(|v| v.to_string()).unwrap_or_default(),
             r.power_now.map(|v| format!("{:.2}", v)).unwrap_or_default(),
             r.energy_now
                 .map(|v| format!("{:.2}", v))
                 .unwrap_or_default(),
-        )?;
+        );
+        if let Err(e) = result {
+            write_err = Some(e);
+        }
+    })?;
+
+    match write_err {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Streams `db`'s readings in `[from, to]` to `w` as JSON Lines (one object
+/// per row), same streaming approach as `export_csv`.
+pub fn export_json(
+    db: &Database,
+    mut w: impl Write,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<(), Box<dyn Error>> {
+    let mut write_err = None;
+    db.for_each_reading(from, to, |r| {
+        if write_err.is_some() {
+            return;
+        }
+        let result = writeln!(
+            w,
+            "{{\"id\":{},\"timestamp\":{},\"datetime\":\"{}\",\"battery\":\"{}\",\"status\":\"{}\",\"capacity\":{},\"power_now\":{},\"energy_now\":{}}}",
+            r.id,
+            r.timestamp,
+            json_escape(&format_timestamp(r.timestamp)),
+            json_escape(&r.battery),
+            r.status,
+            r.capacity.map(|v| v.to_string()).unwrap_or_else(|| "null".to_string()),
+            r.power_now.map(|v| format!("{:.2}", v)).unwrap_or_else(|| "null".to_string()),
+            r.energy_now.map(|v| format!("{:.2}", v)).unwrap_or_else(|| "null".to_string()),
+        );
+        if let Err(e) = result {
+            write_err = Some(e);
+        }
+    })?;
+
+    match write_err {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
     }
+}
+
+/// Outcome of `import_csv`: how many rows were (or, for a dry run, would
+/// have been) inserted, and a per-line report of anything skipped rather
+/// than aborting the whole import over one bad row.
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: Vec<(usize, String)>,
+}
+
+/// Splits one CSV row on unquoted commas, unescaping `""` inside quoted
+/// fields. Mirrors `csv_field`'s quoting so a battery name containing a
+/// comma round-trips instead of shifting every later column.
+fn split_csv_row(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                current.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn parse_row(line: &str) -> Result<ImportRow, String> {
+    let fields = split_csv_row(line);
+    if fields.len() < 8 {
+        return Err(format!("expected 8 columns, found {}", fields.len()));
+    }
+
+    // id (0) and datetime (2) are derived/ignored on import.
+    let timestamp: i64 = fields[1]
+        .parse()
+        .map_err(|_| format!("invalid timestamp {:?}", fields[1]))?;
+    let battery = fields[3].to_string();
+    let status = fields[4]
+        .parse()
+        .map_err(|_| format!("invalid status {:?}", fields[4]))?;
+    let capacity = fields[5].parse().ok();
+    let power_now = fields[6].parse().ok();
+    let energy_now = fields[7].parse().ok();
 
-    Ok(())
+    Ok(ImportRow {
+        timestamp,
+        battery,
+        status,
+        capacity,
+        power_now,
+        energy_now,
+    })
+}
+
+/// Parses a CSV produced by `export_csv` (or compatible) and bulk-inserts
+/// it into `db` inside a single transaction. Malformed rows are collected
+/// into the report's `skipped` list rather than aborting the whole import;
+/// pass `dry_run` to only count what would be imported.
+pub fn import_csv(
+    db: &Database,
+    r: impl Read,
+    dry_run: bool,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let mut lines = io::BufReader::new(r).lines();
+    lines.next(); // header
+
+    let mut rows = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (i, line) in lines.enumerate() {
+        let line_no = i + 2; // 1 for the header, 1-indexed lines
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_row(&line) {
+            Ok(row) => rows.push(row),
+            Err(e) => skipped.push((line_no, e)),
+        }
+    }
+
+    let imported = if dry_run {
+        rows.len()
+    } else {
+        db.import_readings(&rows)?
+    };
+
+    Ok(ImportReport { imported, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_csv_export_import_round_trip() {
+        let src = Database::open(&PathBuf::from(":memory:")).unwrap();
+        src.init_scheme().unwrap();
+        src.insert_reading("BAT0", 1000, "Discharging", Some(80), Some(10.5), Some(40.0))
+            .unwrap();
+        src.insert_reading("BAT0", 2000, "Charging", Some(90), Some(-5.0), Some(45.0))
+            .unwrap();
+
+        let mut csv = Vec::new();
+        export_csv(&src, &mut csv, None, None).unwrap();
+
+        let dst = Database::open(&PathBuf::from(":memory:")).unwrap();
+        dst.init_scheme().unwrap();
+        let report = import_csv(&dst, csv.as_slice(), false).unwrap();
+
+        assert_eq!(report.imported, 2);
+        assert!(report.skipped.is_empty());
+        assert_eq!(dst.count_readings().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_csv_round_trip_survives_comma_in_battery_name() {
+        let src = Database::open(&PathBuf::from(":memory:")).unwrap();
+        src.init_scheme().unwrap();
+        src.insert_reading("Dock, Left", 1000, "Discharging", Some(80), Some(10.5), Some(40.0))
+            .unwrap();
+
+        let mut csv = Vec::new();
+        export_csv(&src, &mut csv, None, None).unwrap();
+
+        let dst = Database::open(&PathBuf::from(":memory:")).unwrap();
+        dst.init_scheme().unwrap();
+        let report = import_csv(&dst, csv.as_slice(), false).unwrap();
+
+        assert_eq!(report.imported, 1);
+        assert!(report.skipped.is_empty());
+
+        let rows = dst.recent_readings("Dock, Left", i64::MIN).unwrap();
+        assert_eq!(rows.len(), 1);
+    }
 }
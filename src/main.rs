@@ -1,17 +1,23 @@
+mod analytics;
 mod battery;
 mod daemon;
 mod db;
 mod export;
+mod sources;
+mod sync;
 
 use battery::{
-    calc_health, find_batteries, get_battery_info, progress_bar, BatteryInfo, BatteryStatus,
+    aggregate_batteries, find_batteries, fit_wear_trend, get_battery_info,
+    set_charge_thresholds, BatteryInfo, BatteryStatus,
 };
+use sources::{discover_sysfs_sources, PowerSource, UpsSource};
 use chrono::{Local, TimeZone};
 use clap::{Parser, Subcommand};
 use colored::*;
 use db::{default_db_path, Database};
 use std::error::Error;
 use std::path::PathBuf;
+use sync::SyncClient;
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -23,6 +29,27 @@ struct Cli {
     /// Show detailed information
     #[arg(short, long)]
     verbose: bool,
+
+    /// Combine all batteries into a single virtual pack
+    #[arg(short, long)]
+    combined: bool,
+
+    /// Custom status line, expanding {name} {capacity} {status} {power}
+    /// {remaining} {health} {bar}
+    #[arg(long)]
+    format: Option<String>,
+
+    /// Emit one JSON object per battery instead of the human-readable view
+    #[arg(long)]
+    json: bool,
+
+    /// Also monitor a UPS via its status-daemon TCP protocol ("host:port")
+    #[arg(long)]
+    ups: Option<String>,
+
+    /// UPS name as known to the UPS daemon
+    #[arg(long, default_value = "ups")]
+    ups_name: String,
 }
 
 #[derive(Subcommand)]
@@ -36,6 +63,13 @@ enum Commands {
     // Show status about daemon and stored data
     Status,
 
+    /// Live tail of battery status/capacity transitions
+    Watch {
+        /// Poll interval in seconds
+        #[arg(short, long, default_value = "2")]
+        interval: u64,
+    },
+
     /// Export data to CSV
     Export {
         /// Output file path (stdout if not specified)
@@ -49,7 +83,111 @@ enum Commands {
         /// End date (YYYY-MM-DD)
         #[arg(long)]
         to: Option<String>,
+
+        /// Export as JSON Lines instead of CSV
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Import readings from a CSV previously produced by `export`
+    Import {
+        /// CSV file path (stdin if not specified)
+        input: Option<PathBuf>,
+
+        /// Count rows that would be imported without writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show capacity-fade trend and projected end-of-life
+    Health {
+        /// Battery to analyze (defaults to the first one found)
+        #[arg(long)]
+        battery: Option<String>,
+
+        /// Fraction of design capacity considered end-of-life
+        #[arg(long, default_value = "0.8")]
+        threshold: f32,
+    },
+
+    /// Roll up raw readings into hourly/daily aggregates and prune old rows
+    Compact {
+        /// Raw readings older than this many days, counted back to the
+        /// preceding UTC midnight, are deleted after rollup
+        #[arg(long, default_value = "30")]
+        retention_days: i64,
+    },
+
+    /// Show hourly/daily aggregates produced by `compact`, or a paginated
+    /// dump of raw readings with `--raw`
+    History {
+        /// Start date (YYYY-MM-DD), ignored with --raw
+        #[arg(long)]
+        from: Option<String>,
+
+        /// End date (YYYY-MM-DD), ignored with --raw
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Rollup granularity to read (defaults to auto-selecting by span)
+        #[arg(long, value_enum, default_value = "auto")]
+        rollup: RollupArg,
+
+        /// Page through raw (non-rolled-up) readings instead
+        #[arg(long)]
+        raw: bool,
+
+        /// Raw readings fetched per page
+        #[arg(long, default_value = "50")]
+        limit: usize,
     },
+
+    /// Read or set charge-control thresholds
+    Limit {
+        /// Battery to target (defaults to the first one found)
+        #[arg(long)]
+        battery: Option<String>,
+
+        /// Charge start threshold (%)
+        #[arg(long)]
+        start: Option<u32>,
+
+        /// Charge end threshold (%)
+        #[arg(long)]
+        end: Option<u32>,
+    },
+
+    /// Push or pull history via a sync peer (juice itself does not serve
+    /// the peer side)
+    Sync {
+        /// Peer address ("host:port")
+        endpoint: String,
+
+        /// File holding the shared sync key (raw bytes, kept off-wire)
+        #[arg(long)]
+        key_file: PathBuf,
+
+        /// Pull the peer's readings instead of pushing local ones
+        #[arg(long)]
+        pull: bool,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum RollupArg {
+    Auto,
+    Hourly,
+    Daily,
+}
+
+impl From<RollupArg> for db::RollupResolution {
+    fn from(r: RollupArg) -> Self {
+        match r {
+            RollupArg::Auto => db::RollupResolution::Auto,
+            RollupArg::Hourly => db::RollupResolution::Hourly,
+            RollupArg::Daily => db::RollupResolution::Daily,
+        }
+    }
 }
 
 impl BatteryInfo {
@@ -97,6 +235,14 @@ impl BatteryInfo {
     }
 }
 
+/// Renders a capacity percentage as a filled/empty block bar, `width`
+/// blocks wide.
+fn progress_bar(percent: u32, width: u32) -> ColoredString {
+    let filled = (percent * width / 100).min(width) as usize;
+    let empty = width as usize - filled;
+    format!("{}{}", "█".repeat(filled), "░".repeat(empty)).normal()
+}
+
 fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
@@ -133,6 +279,22 @@ fn format_duration(first: i64, last: i64) -> String {
     }
 }
 
+/// Resolves a `--battery` name to its sysfs path, defaulting to the first
+/// battery found when no name is given.
+fn resolve_battery_path(name: Option<&str>) -> Result<PathBuf, Box<dyn Error>> {
+    let paths = find_batteries();
+    match name {
+        Some(name) => paths
+            .into_iter()
+            .find(|p| p.file_name().and_then(|s| s.to_str()) == Some(name))
+            .ok_or_else(|| format!("Battery '{}' not found", name).into()),
+        None => paths
+            .into_iter()
+            .next()
+            .ok_or_else(|| "No battery found".into()),
+    }
+}
+
 fn parse_date(s: &str) -> Option<i64> {
     use chrono::NaiveDate;
     NaiveDate::parse_from_str(s, "%Y-%m-%d")
@@ -141,6 +303,48 @@ fn parse_date(s: &str) -> Option<i64> {
         .map(|dt| dt.and_utc().timestamp())
 }
 
+/// Expands a `--format` template against a single battery's fields.
+fn expand_format(template: &str, info: &BatteryInfo) -> String {
+    let health_str = info.calc_health()
+        .map(|h| format!("{:.0}%", h))
+        .unwrap_or_else(|| "--%".to_string());
+
+    template
+        .replace("{name}", &info.name)
+        .replace("{capacity}", &info.capacity_str())
+        .replace("{status}", &info.status.to_string())
+        .replace("{power}", &info.power_str())
+        .replace("{remaining}", &info.remaining_str())
+        .replace("{health}", &health_str)
+        .replace("{bar}", &info.bar().to_string())
+}
+
+/// Escapes a string for embedding in the hand-rolled JSON emitted by
+/// `--json`; there's no serde dependency here, just like `export::export_csv`
+/// hand-rolls its own rows.
+fn json_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn print_json(info: &BatteryInfo) {
+    println!(
+        "{{\"name\":\"{}\",\"capacity\":{},\"status\":\"{}\",\"state\":{},\"power\":{},\"remaining\":\"{}\",\"health\":{}}}",
+        json_escape(&info.name),
+        info.capacity
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "null".to_string()),
+        info.status,
+        info.status.code(),
+        info.power_now
+            .map(|p| format!("{:.2}", p))
+            .unwrap_or_else(|| "null".to_string()),
+        info.remaining_str(),
+        info.calc_health()
+            .map(|h| format!("{:.1}", h))
+            .unwrap_or_else(|| "null".to_string()),
+    );
+}
+
 fn print_normal(info: &BatteryInfo) {
     let charging_symbol = match info.status {
         BatteryStatus::Charging => "↑".yellow(),
@@ -160,7 +364,7 @@ fn print_normal(info: &BatteryInfo) {
     );
 }
 
-fn print_verbose(info: &BatteryInfo) {
+fn print_verbose(info: &BatteryInfo, db: Option<&Database>) {
     let bar = info
         .capacity
         .map(|n| progress_bar(n, 10))
@@ -177,7 +381,7 @@ fn print_verbose(info: &BatteryInfo) {
         .map(|n| n.to_string())
         .unwrap_or_else(|| "Unknown".to_string());
 
-    let health_str = calc_health(info)
+    let health_str = info.calc_health()
         .map(|n| format!("{:.1}%", n))
         .unwrap_or_else(|| " --%".to_string());
 
@@ -190,6 +394,14 @@ fn print_verbose(info: &BatteryInfo) {
     );
     println!("  Power:       {:<}", info.power_str());
     println!("  Remaining:   {:<}", info.remaining_str());
+
+    if let Some(smoothed) = db.and_then(|db| info.calc_smoothed_remaining_time(db)) {
+        println!(
+            "  Smoothed remaining: {}h{:02}m",
+            smoothed.0, smoothed.1
+        );
+    }
+
     println!("  Energy:      {:<}", energy_str);
     println!("  Cycle count: {:<}", cycle_count_str);
     println!("  Health:      {:<}", health_str);
@@ -197,6 +409,10 @@ fn print_verbose(info: &BatteryInfo) {
         "  Technology:  {}",
         info.technology.as_deref().unwrap_or("Unknown")
     );
+
+    if let (Some(start), Some(end)) = (info.charge_start_threshold, info.charge_end_threshold) {
+        println!("  Charge limit: {}% - {}%", start, end);
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -204,19 +420,47 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     match cli.command {
         None => {
-            let battery_paths = find_batteries();
+            let mut power_sources: Vec<Box<dyn PowerSource>> = discover_sysfs_sources()
+                .into_iter()
+                .map(|s| Box::new(s) as Box<dyn PowerSource>)
+                .collect();
+
+            if let Some(ups) = &cli.ups {
+                let (host, port) = ups
+                    .split_once(':')
+                    .ok_or("--ups must be in \"host:port\" form")?;
+                let port: u16 = port.parse()?;
+                power_sources.push(Box::new(UpsSource::new(host, port, cli.ups_name.clone())));
+            }
+
+            let infos: Vec<BatteryInfo> = power_sources
+                .iter()
+                .filter(|s| s.is_available())
+                .map(|s| s.read_info())
+                .collect();
 
-            if battery_paths.is_empty() {
+            if infos.is_empty() {
                 println!("No battery found");
                 return Ok(());
             }
 
-            for path in battery_paths {
-                let battery_info = get_battery_info(&path);
-                if cli.verbose {
-                    print_verbose(&battery_info);
+            let db = Database::open(&default_db_path()).ok();
+
+            let infos = if cli.combined {
+                aggregate_batteries(&infos).into_iter().collect()
+            } else {
+                infos
+            };
+
+            for info in &infos {
+                if cli.json {
+                    print_json(info);
+                } else if let Some(template) = &cli.format {
+                    println!("{}", expand_format(template, info));
+                } else if cli.verbose {
+                    print_verbose(info, db.as_ref());
                 } else {
-                    print_normal(&battery_info);
+                    print_normal(info);
                 }
             }
         }
@@ -225,6 +469,9 @@ fn main() -> Result<(), Box<dyn Error>> {
             println!("Starting daemon with {}s interval...", interval);
             daemon::run(db_path, interval)?;
         }
+        Some(Commands::Watch { interval }) => {
+            daemon::watch(interval)?;
+        }
         Some(Commands::Status) => {
             let db_path = default_db_path();
             let file_size = std::fs::metadata(&db_path).map(|m| m.len()).unwrap_or(0);
@@ -253,23 +500,232 @@ fn main() -> Result<(), Box<dyn Error>> {
                 Err(e) => println!("Database error: {}", e),
             }
         }
-        Some(Commands::Export { output, from, to }) => {
+        Some(Commands::Export {
+            output,
+            from,
+            to,
+            json,
+        }) => {
             let db_path = default_db_path();
             let db = Database::open(&db_path)?;
 
             let from_timestamp = from.as_ref().and_then(|s| parse_date(s));
             let to_timestamp = to.as_ref().and_then(|s| parse_date(s));
 
-            let readings = db.get_readings(from_timestamp, to_timestamp)?;
-
             match output {
                 Some(path) => {
                     let file = std::fs::File::create(path)?;
-                    export::write_csv(file, &readings)?;
+                    if json {
+                        export::export_json(&db, file, from_timestamp, to_timestamp)?;
+                    } else {
+                        export::export_csv(&db, file, from_timestamp, to_timestamp)?;
+                    }
                 }
                 None => {
-                    export::write_csv(std::io::stdout(), &readings)?;
+                    if json {
+                        export::export_json(&db, std::io::stdout(), from_timestamp, to_timestamp)?;
+                    } else {
+                        export::export_csv(&db, std::io::stdout(), from_timestamp, to_timestamp)?;
+                    }
+                }
+            }
+        }
+        Some(Commands::Import { input, dry_run }) => {
+            let db_path = default_db_path();
+            let db = Database::open(&db_path)?;
+
+            let report = match input {
+                Some(path) => export::import_csv(&db, std::fs::File::open(path)?, dry_run)?,
+                None => export::import_csv(&db, std::io::stdin(), dry_run)?,
+            };
+
+            if dry_run {
+                println!("Would import {} rows", report.imported);
+            } else {
+                println!("Imported {} rows", report.imported);
+            }
+
+            if !report.skipped.is_empty() {
+                println!("Skipped {} malformed rows:", report.skipped.len());
+                for (line, reason) in &report.skipped {
+                    println!("  line {}: {}", line, reason);
+                }
+            }
+        }
+        Some(Commands::Health { battery, threshold }) => {
+            let db_path = default_db_path();
+            let db = Database::open(&db_path)?;
+
+            let battery_path = resolve_battery_path(battery.as_deref())?;
+            let battery_name = battery_path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Unknown")
+                .to_string();
+
+            let design_capacity = get_battery_info(&battery_path)
+                .energy_full_design
+                .ok_or("No design capacity reported for this battery")?;
+
+            let samples = db.full_energy_samples(&battery_name)?;
+
+            match fit_wear_trend(&samples, design_capacity, threshold) {
+                Some(trend) => {
+                    println!("Battery:         {}", battery_name);
+                    println!("Wear rate:       {:.2} Wh/month", trend.wear_per_month);
+                    match trend.projected_eol {
+                        Some(ts) => println!(
+                            "Projected EOL:   {} ({:.0}% threshold)",
+                            format_timestamp(ts),
+                            threshold * 100.0
+                        ),
+                        None => println!("Projected EOL:   not trending toward threshold"),
+                    }
+                }
+                None => println!(
+                    "Not enough history yet (need at least 5 days of 'Full' readings for {})",
+                    battery_name
+                ),
+            }
+
+            if let Some(report) = analytics::health_report(&db, &battery_name, design_capacity)? {
+                println!("Charge cycles:   {}", report.samples);
+                if let Some(pct) = report.wear_percent {
+                    println!("Present health:  {:.1}% of design capacity", pct);
+                }
+                match report.degradation_per_month {
+                    Some(wh) => println!("Degradation:     {:.2} Wh/month", wh),
+                    None => println!("Degradation:     not enough cycles yet"),
                 }
+                match report.est_full_runtime_secs {
+                    Some(secs) => println!(
+                        "Full runtime:    ~{}h {}m (typical discharge rate)",
+                        secs / 3600,
+                        (secs % 3600) / 60
+                    ),
+                    None => println!("Full runtime:    not enough discharge history yet"),
+                }
+            }
+        }
+        Some(Commands::Compact { retention_days }) => {
+            let db_path = default_db_path();
+            let db = Database::open(&db_path)?;
+
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+
+            db.compact(now, retention_days * 86400)?;
+            println!("Compacted readings (retention: {} days)", retention_days);
+        }
+        Some(Commands::History {
+            from,
+            to,
+            rollup,
+            raw,
+            limit,
+        }) => {
+            let db_path = default_db_path();
+            let db = Database::open(&db_path)?;
+
+            if raw {
+                let mut cursor = None;
+                let mut printed = 0;
+                loop {
+                    let (page, next) = db.get_readings_page(cursor, limit)?;
+                    if page.is_empty() {
+                        break;
+                    }
+
+                    for r in &page {
+                        println!(
+                            "{} {:<8} {:<11} cap {:>5} power {:>7}",
+                            format_timestamp(r.timestamp),
+                            r.battery,
+                            r.status.to_string(),
+                            r.capacity
+                                .map(|v| format!("{}%", v))
+                                .unwrap_or_else(|| "--".to_string()),
+                            r.power_now
+                                .map(|v| format!("{:.1}W", v))
+                                .unwrap_or_else(|| "--".to_string()),
+                        );
+                    }
+
+                    printed += page.len();
+                    if page.len() < limit {
+                        break;
+                    }
+                    cursor = next;
+                }
+
+                if printed == 0 {
+                    println!("No readings recorded yet");
+                }
+            } else {
+                let from_timestamp = from.as_ref().and_then(|s| parse_date(s)).unwrap_or(i64::MIN);
+                let to_timestamp = to.as_ref().and_then(|s| parse_date(s)).unwrap_or(i64::MAX);
+
+                let rows = db.get_readings_rollup(from_timestamp, to_timestamp, rollup.into())?;
+                if rows.is_empty() {
+                    println!("No rollup data in range");
+                }
+                for r in rows {
+                    println!(
+                        "{} {:<8} cap {:>5} power {:>7} energy {:>8} ({} samples)",
+                        format_timestamp(r.bucket_start),
+                        r.battery,
+                        r.capacity_avg
+                            .map(|v| format!("{:.0}%", v))
+                            .unwrap_or_else(|| "--".to_string()),
+                        r.power_now_avg
+                            .map(|v| format!("{:.1}W", v))
+                            .unwrap_or_else(|| "--".to_string()),
+                        r.energy_now_avg
+                            .map(|v| format!("{:.1}Wh", v))
+                            .unwrap_or_else(|| "--".to_string()),
+                        r.sample_count,
+                    );
+                }
+            }
+        }
+        Some(Commands::Limit {
+            battery,
+            start,
+            end,
+        }) => {
+            let battery_path = resolve_battery_path(battery.as_deref())?;
+
+            if start.is_none() && end.is_none() {
+                let info = get_battery_info(&battery_path);
+                match (info.charge_start_threshold, info.charge_end_threshold) {
+                    (Some(start), Some(end)) => {
+                        println!("Charge limit: {}% - {}%", start, end)
+                    }
+                    _ => println!("This battery does not expose charge-control thresholds"),
+                }
+            } else {
+                set_charge_thresholds(&battery_path, start, end)?;
+                println!("Charge limit updated");
+            }
+        }
+        Some(Commands::Sync {
+            endpoint,
+            key_file,
+            pull,
+        }) => {
+            let db_path = default_db_path();
+            let db = Database::open(&db_path)?;
+            let key = std::fs::read(&key_file)?;
+            let client = SyncClient::new(endpoint, key);
+
+            if pull {
+                let n = client.pull(&db)?;
+                println!("Pulled {} new reading(s)", n);
+            } else {
+                let n = client.push(&db)?;
+                println!("Pushed {} reading(s)", n);
             }
         }
     }
@@ -0,0 +1,166 @@
+use crate::battery::{find_batteries, get_battery_info, BatteryInfo, BatteryStatus};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+/// A place `juice` can read a `BatteryInfo` from. The sysfs reader in
+/// `battery.rs` is one implementation; a networked UPS is another, so a
+/// desktop with no internal battery can still be monitored and logged
+/// through the same `Database` and `export` paths as a laptop pack.
+pub trait PowerSource {
+    /// Whether this source can currently be read (battery present, UPS
+    /// daemon reachable, ...).
+    fn is_available(&self) -> bool;
+
+    /// Reads the current state. Implementations should return a
+    /// best-effort `BatteryInfo` with `Unknown`/`None` fields when not
+    /// available rather than panicking.
+    fn read_info(&self) -> BatteryInfo;
+}
+
+/// Reads a single pack under `/sys/class/power_supply/<name>`.
+pub struct SysfsSource {
+    path: PathBuf,
+}
+
+impl SysfsSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl PowerSource for SysfsSource {
+    fn is_available(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read_info(&self) -> BatteryInfo {
+        get_battery_info(&self.path)
+    }
+}
+
+/// Every battery under `/sys/class/power_supply`, wrapped as `PowerSource`s.
+pub fn discover_sysfs_sources() -> Vec<SysfsSource> {
+    find_batteries().into_iter().map(SysfsSource::new).collect()
+}
+
+/// Reads charge/load/status from a UPS daemon speaking the common
+/// `GET VAR <ups> <variable>` line protocol (as used by NUT) over TCP.
+pub struct UpsSource {
+    host: String,
+    port: u16,
+    name: String,
+}
+
+impl UpsSource {
+    pub fn new(host: impl Into<String>, port: u16, name: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            name: name.into(),
+        }
+    }
+
+    fn query_var(&self, variable: &str) -> io::Result<String> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        writeln!(stream, "GET VAR {} {}", self.name, variable)?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        // Response looks like: VAR <ups> <variable> "value"
+        line.split('"')
+            .nth(1)
+            .map(|s| s.to_string())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected UPS response"))
+    }
+
+    fn query(&self) -> io::Result<BatteryInfo> {
+        let capacity = self
+            .query_var("battery.charge")
+            .ok()
+            .and_then(|s| s.parse().ok());
+
+        let power_now = self
+            .query_var("ups.realpower")
+            .ok()
+            .and_then(|s| s.parse().ok());
+
+        let raw_status = self.query_var("ups.status")?;
+        let status = parse_ups_status(&raw_status);
+
+        Ok(BatteryInfo {
+            name: self.name.clone(),
+            status,
+            capacity,
+            cycle_count: None,
+            power_now,
+            energy_now: None,
+            energy_full: None,
+            energy_full_design: None,
+            technology: Some("UPS".to_string()),
+            charge_start_threshold: None,
+            charge_end_threshold: None,
+        })
+    }
+}
+
+/// Maps a NUT `ups.status` value (space-separated flags, e.g. `"OL CHRG"`)
+/// to a `BatteryStatus`. Flags are matched as whole tokens, not substrings,
+/// since `DISCHRG` contains `CHRG` and a naive `contains` would report a
+/// discharging UPS as charging. `OB`/`DISCHRG` takes priority over `CHRG`
+/// since a UPS can report multiple flags at once; `OL` without `CHRG` is
+/// `Full`, and anything else is `Unknown` rather than a guess.
+fn parse_ups_status(raw: &str) -> BatteryStatus {
+    let flags: Vec<&str> = raw.split_whitespace().collect();
+    if flags.contains(&"OB") || flags.contains(&"DISCHRG") {
+        BatteryStatus::Discharging
+    } else if flags.contains(&"CHRG") {
+        BatteryStatus::Charging
+    } else if flags.contains(&"OL") {
+        BatteryStatus::Full
+    } else {
+        BatteryStatus::Unknown
+    }
+}
+
+impl PowerSource for UpsSource {
+    fn is_available(&self) -> bool {
+        TcpStream::connect((self.host.as_str(), self.port)).is_ok()
+    }
+
+    fn read_info(&self) -> BatteryInfo {
+        self.query().unwrap_or_else(|_| BatteryInfo {
+            name: self.name.clone(),
+            status: BatteryStatus::Unknown,
+            capacity: None,
+            cycle_count: None,
+            power_now: None,
+            energy_now: None,
+            energy_full: None,
+            energy_full_design: None,
+            technology: Some("UPS".to_string()),
+            charge_start_threshold: None,
+            charge_end_threshold: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ups_status_maps_known_flags() {
+        assert_eq!(parse_ups_status("OL CHRG"), BatteryStatus::Charging);
+        assert_eq!(parse_ups_status("OB DISCHRG"), BatteryStatus::Discharging);
+        assert_eq!(parse_ups_status("OL"), BatteryStatus::Full);
+    }
+
+    #[test]
+    fn test_parse_ups_status_unknown_flag_is_unknown() {
+        assert_eq!(parse_ups_status("BYPASS"), BatteryStatus::Unknown);
+        assert_eq!(parse_ups_status(""), BatteryStatus::Unknown);
+    }
+}